@@ -0,0 +1,398 @@
+//! Line-level diffing used to build gawsh's per-commit unified diff pages. The file-discovery
+//! side (which paths changed between two trees) is handled by `git2::Diff` in `main.rs`; this
+//! module only owns turning a pair of blobs' lines into a unified-diff hunk list, plus the
+//! similarity scoring used for rename/copy detection.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// A single edit-script operation, referencing indices into the old/new line slices it was
+/// computed over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditOp {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Above this many combined old+new lines, `myers_diff` skips the edit-script search entirely and
+/// returns a single coarse delete-everything/insert-everything script.
+///
+/// The search below is Myers' linear-space refinement, so it no longer risks the O((N+M)²)
+/// *memory* blowup the previous full-trace implementation had (measured: ~2.3GB resident for a
+/// 6000-line rewrite). It's still O(ND) *time*, though, same as any Myers variant, and gawsh
+/// renders every commit's diffs in parallel across rayon workers — one worker stuck for tens of
+/// seconds computing an optimal script for a multi-megabyte minified bundle or lockfile rewrite
+/// still stalls that worker's share of the render. Past this threshold the optimal script isn't
+/// worth the wait: nobody reads a generated-file diff hunk-by-hunk anyway.
+const MYERS_DIFF_MAX_COMBINED_LINES: usize = 50_000;
+
+/// Computes the shortest edit script between two line sequences via Myers' diff algorithm,
+/// returning the ops in old/new (forward) order. Uses the linear-space divide-and-conquer
+/// refinement (find a middle snake, recurse on either side of it) rather than the classic
+/// full-trace backtrack, so memory stays O(N+M) regardless of how dissimilar `old` and `new` are.
+/// See `MYERS_DIFF_MAX_COMBINED_LINES` for the point past which this gives up on an optimal script.
+pub fn myers_diff(old: &[&str], new: &[&str]) -> Vec<EditOp> {
+    if old.len() + new.len() > MYERS_DIFF_MAX_COMBINED_LINES {
+        let mut ops = Vec::with_capacity(old.len() + new.len());
+        ops.extend((0..old.len()).map(EditOp::Delete));
+        ops.extend((0..new.len()).map(EditOp::Insert));
+        return ops;
+    }
+
+    let mut ops = Vec::new();
+    diff_range(old, new, 0, old.len(), 0, new.len(), &mut ops);
+    ops
+}
+
+/// Recursively diffs `old[old_lo..old_hi]` against `new[new_lo..new_hi]`, trimming any common
+/// prefix/suffix directly, then splitting the remainder on a middle snake (see
+/// `find_middle_snake`) and recursing on the two halves either side of it. Pushes ops onto `ops`
+/// in forward order as it goes.
+fn diff_range(
+    old: &[&str],
+    new: &[&str],
+    mut old_lo: usize,
+    mut old_hi: usize,
+    mut new_lo: usize,
+    mut new_hi: usize,
+    ops: &mut Vec<EditOp>,
+) {
+    while old_lo < old_hi && new_lo < new_hi && old[old_lo] == new[new_lo] {
+        ops.push(EditOp::Equal(old_lo, new_lo));
+        old_lo += 1;
+        new_lo += 1;
+    }
+
+    let mut trailing_equal = Vec::new();
+    while old_hi > old_lo && new_hi > new_lo && old[old_hi - 1] == new[new_hi - 1] {
+        trailing_equal.push(EditOp::Equal(old_hi - 1, new_hi - 1));
+        old_hi -= 1;
+        new_hi -= 1;
+    }
+
+    if old_lo == old_hi {
+        ops.extend((new_lo..new_hi).map(EditOp::Insert));
+    } else if new_lo == new_hi {
+        ops.extend((old_lo..old_hi).map(EditOp::Delete));
+    } else if let Some((snake_start_x, snake_start_y, snake_end_x, snake_end_y)) =
+        find_middle_snake(old, new, old_lo, old_hi, new_lo, new_hi)
+    {
+        diff_range(old, new, old_lo, snake_start_x, new_lo, snake_start_y, ops);
+        for i in 0..(snake_end_x - snake_start_x) {
+            ops.push(EditOp::Equal(snake_start_x + i, snake_start_y + i));
+        }
+        diff_range(old, new, snake_end_x, old_hi, snake_end_y, new_hi, ops);
+    }
+
+    ops.extend(trailing_equal.into_iter().rev());
+}
+
+/// Finds a "middle snake" splitting the Myers edit graph for `old[old_lo..old_hi]` vs.
+/// `new[new_lo..new_hi]`, per the linear-space refinement in Myers' 1986 paper (section 4b): run
+/// the forward search from (old_lo, new_lo) and the backward search from (old_hi, new_hi)
+/// simultaneously, one D at a time, until a diagonal the two searches share overlaps. That
+/// overlap point is the middle snake — the diff of each half either side of it, found by
+/// recursing in `diff_range`, together gives a shortest edit script for the whole range.
+///
+/// Only ever needs O(old_hi-old_lo + new_hi-new_lo) space (the two work arrays below), regardless
+/// of how far apart `old` and `new` are, which is what keeps `myers_diff`'s total memory linear
+/// instead of the classic full-trace algorithm's quadratic blowup.
+///
+/// Callers only reach this once the empty-range cases are handled, so both ranges are non-empty.
+fn find_middle_snake(
+    old: &[&str],
+    new: &[&str],
+    old_lo: usize,
+    old_hi: usize,
+    new_lo: usize,
+    new_hi: usize,
+) -> Option<(usize, usize, usize, usize)> {
+    let n = (old_hi - old_lo) as isize;
+    let m = (new_hi - new_lo) as isize;
+    let max = n + m;
+    let delta = n - m;
+    let offset = max;
+    let mut vf: Vec<isize> = vec![0; (2 * max + 1) as usize];
+    let mut vb: Vec<isize> = vec![0; (2 * max + 1) as usize];
+
+    for d in 0..=((max + 1) / 2) {
+        let mut k = -d;
+        while k <= d {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && vf[idx - 1] < vf[idx + 1]) {
+                vf[idx + 1]
+            } else {
+                vf[idx - 1] + 1
+            };
+            let mut y = x - k;
+            let (start_x, start_y) = (x, y);
+
+            while x < n
+                && y < m
+                && old[(old_lo as isize + x) as usize] == new[(new_lo as isize + y) as usize]
+            {
+                x += 1;
+                y += 1;
+            }
+            vf[idx] = x;
+
+            if delta % 2 != 0 && k >= delta - (d - 1) && k <= delta + (d - 1) {
+                let back_idx = (delta - k + offset) as usize;
+                if vb[back_idx] + x >= n {
+                    return Some((
+                        (old_lo as isize + start_x) as usize,
+                        (new_lo as isize + start_y) as usize,
+                        (old_lo as isize + x) as usize,
+                        (new_lo as isize + y) as usize,
+                    ));
+                }
+            }
+            k += 2;
+        }
+
+        let mut k = -d;
+        while k <= d {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && vb[idx - 1] < vb[idx + 1]) {
+                vb[idx + 1]
+            } else {
+                vb[idx - 1] + 1
+            };
+            let mut y = x - k;
+            let (start_x, start_y) = (x, y);
+
+            while x < n
+                && y < m
+                && old[(old_hi as isize - x - 1) as usize]
+                    == new[(new_hi as isize - y - 1) as usize]
+            {
+                x += 1;
+                y += 1;
+            }
+            vb[idx] = x;
+
+            if delta % 2 == 0 && k >= delta - d && k <= delta + d {
+                let fwd_idx = (delta - k + offset) as usize;
+                if vf[fwd_idx] + x >= n {
+                    return Some((
+                        (old_hi as isize - x) as usize,
+                        (new_hi as isize - y) as usize,
+                        (old_hi as isize - start_x) as usize,
+                        (new_hi as isize - start_y) as usize,
+                    ));
+                }
+            }
+            k += 2;
+        }
+    }
+
+    None
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowKind {
+    Context,
+    Added,
+    Removed,
+}
+
+/// One rendered row of a diff hunk. `content` starts out as the plain source line and is swapped
+/// for syntax-highlighted HTML by the caller once rows have been built.
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub kind: RowKind,
+    pub old_lineno: Option<usize>,
+    pub new_lineno: Option<usize>,
+    pub content: String,
+}
+
+/// Expands an edit script into 1-indexed, line-numbered rows.
+pub fn rows_from_ops(old: &[&str], new: &[&str], ops: &[EditOp]) -> Vec<Row> {
+    ops.iter()
+        .map(|op| match *op {
+            EditOp::Equal(oi, ni) => Row {
+                kind: RowKind::Context,
+                old_lineno: Some(oi + 1),
+                new_lineno: Some(ni + 1),
+                content: old[oi].to_string(),
+            },
+            EditOp::Delete(oi) => Row {
+                kind: RowKind::Removed,
+                old_lineno: Some(oi + 1),
+                new_lineno: None,
+                content: old[oi].to_string(),
+            },
+            EditOp::Insert(ni) => Row {
+                kind: RowKind::Added,
+                old_lineno: None,
+                new_lineno: Some(ni + 1),
+                content: new[ni].to_string(),
+            },
+        })
+        .collect()
+}
+
+/// A unified-diff hunk: a contiguous run of rows, padded with up to `context` lines of
+/// surrounding unchanged rows on either side.
+#[derive(Debug, Clone)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub rows: Vec<Row>,
+}
+
+impl Hunk {
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_lines, self.new_start, self.new_lines
+        )
+    }
+}
+
+/// Groups a flat row list into hunks, padding each changed run with up to `context` lines of
+/// surrounding context and merging any hunks whose context windows end up overlapping.
+pub fn group_hunks(rows: Vec<Row>, context: usize) -> Vec<Hunk> {
+    if rows.is_empty() {
+        return Vec::new();
+    }
+
+    let mut changed_ranges: Vec<(usize, usize)> = Vec::new();
+    let mut i = 0;
+    while i < rows.len() {
+        if rows[i].kind != RowKind::Context {
+            let start = i;
+            let mut end = i;
+            while end + 1 < rows.len() && rows[end + 1].kind != RowKind::Context {
+                end += 1;
+            }
+            changed_ranges.push((start, end));
+            i = end + 1;
+        } else {
+            i += 1;
+        }
+    }
+
+    let mut windows: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in changed_ranges {
+        let window_start = start.saturating_sub(context);
+        let window_end = (end + context).min(rows.len() - 1);
+
+        match windows.last_mut() {
+            Some(last) if window_start <= last.1 + 1 => last.1 = last.1.max(window_end),
+            _ => windows.push((window_start, window_end)),
+        }
+    }
+
+    windows
+        .into_iter()
+        .map(|(start, end)| {
+            let slice = rows[start..=end].to_vec();
+            let old_start = slice.iter().find_map(|r| r.old_lineno).unwrap_or(0);
+            let new_start = slice.iter().find_map(|r| r.new_lineno).unwrap_or(0);
+            let old_lines = slice.iter().filter(|r| r.old_lineno.is_some()).count();
+            let new_lines = slice.iter().filter(|r| r.new_lineno.is_some()).count();
+
+            Hunk {
+                old_start,
+                old_lines,
+                new_start,
+                new_lines,
+                rows: slice,
+            }
+        })
+        .collect()
+}
+
+/// A multiset of line hashes for a blob's content, used for similarity scoring in rename/copy
+/// detection. Keyed by a simple `u64` hash of each line rather than the line text itself, so
+/// comparing two files stays cheap even for large ones.
+pub fn line_hash_multiset(content: &str) -> HashMap<u64, u32> {
+    let mut set = HashMap::new();
+    for line in content.lines() {
+        let mut hasher = DefaultHasher::new();
+        line.hash(&mut hasher);
+        *set.entry(hasher.finish()).or_insert(0) += 1;
+    }
+    set
+}
+
+/// Dice-coefficient-style similarity between two line-hash multisets: `2 * shared / (old + new)`.
+pub fn similarity(old: &HashMap<u64, u32>, new: &HashMap<u64, u32>) -> f32 {
+    let old_total: u32 = old.values().sum();
+    let new_total: u32 = new.values().sum();
+
+    if old_total + new_total == 0 {
+        return 1.0;
+    }
+
+    let shared: u32 = old
+        .iter()
+        .map(|(hash, count)| new.get(hash).map_or(0, |other| (*count).min(*other)))
+        .sum();
+
+    2.0 * shared as f32 / (old_total + new_total) as f32
+}
+
+/// A detected rename or copy pairing between a deleted candidate and an added candidate. The
+/// `*_index` fields are whatever the caller passed in alongside each candidate's content (we never
+/// interpret them), so the caller can map a match straight back to its own bookkeeping.
+#[derive(Debug, Clone, Copy)]
+pub struct RenameMatch {
+    pub deleted_index: usize,
+    pub added_index: usize,
+    pub score: f32,
+}
+
+/// Greedily pairs deleted candidates with added candidates in descending similarity order,
+/// discarding pairings below `threshold`. Resolving conflicts greedily in descending-score order
+/// keeps the result deterministic even when a file could plausibly match more than one candidate.
+pub fn detect_renames(
+    deleted: &[(usize, String)],
+    added: &[(usize, String)],
+    threshold: f32,
+) -> Vec<RenameMatch> {
+    let deleted_sets: Vec<HashMap<u64, u32>> = deleted
+        .iter()
+        .map(|(_, content)| line_hash_multiset(content))
+        .collect();
+    let added_sets: Vec<HashMap<u64, u32>> = added
+        .iter()
+        .map(|(_, content)| line_hash_multiset(content))
+        .collect();
+
+    let mut candidates: Vec<(f32, usize, usize)> = Vec::new();
+    for (d_idx, d_set) in deleted_sets.iter().enumerate() {
+        for (a_idx, a_set) in added_sets.iter().enumerate() {
+            let score = similarity(d_set, a_set);
+            if score >= threshold {
+                candidates.push((score, d_idx, a_idx));
+            }
+        }
+    }
+
+    candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap().then(a.1.cmp(&b.1)));
+
+    let mut used_deleted = vec![false; deleted.len()];
+    let mut used_added = vec![false; added.len()];
+    let mut matches = Vec::new();
+
+    for (score, d_idx, a_idx) in candidates {
+        if used_deleted[d_idx] || used_added[a_idx] {
+            continue;
+        }
+        used_deleted[d_idx] = true;
+        used_added[a_idx] = true;
+        matches.push(RenameMatch {
+            deleted_index: deleted[d_idx].0,
+            added_index: added[a_idx].0,
+            score,
+        });
+    }
+
+    matches
+}
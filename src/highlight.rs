@@ -0,0 +1,113 @@
+//! Manual scope-stack highlighting, replacing `ClassedHTMLGenerator` everywhere gawsh needs each
+//! *stored* line to be complete, self-contained HTML.
+//!
+//! `ClassedHTMLGenerator` highlights a whole blob into one HTML string and only gets split back
+//! into lines afterward (on `\n`). That's fine as long as every scope syntect pushes is popped
+//! again before the line ends, but isn't true for multi-line constructs (block comments, heredocs,
+//! …): the `<span>` they open can dangle open across the split, leaving the line it was split out
+//! of invalid HTML on its own. Since `RenderedObject` anchors and serves individual lines, that
+//! matters here in a way it wouldn't for a single monolithic page.
+//!
+//! This module drives `syntect`'s lower-level `ParseState`/`ScopeStack` API directly instead: parse
+//! one line at a time, track the resulting scope stack ourselves, and close every span still open
+//! at the end of a line before reopening the same stack at the start of the next one.
+
+use syntect::html::ClassStyle;
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet, SCOPE_REPO};
+use syntect::util::LinesWithEndings;
+
+/// Translates one scope's atom chain into a `class="..."` attribute value, the same way
+/// `ClassedHTMLGenerator` does: one space-separated class per atom, each optionally
+/// `gawsh-`-prefixed per `class_style`.
+fn scope_to_classes(scope: Scope, class_style: ClassStyle) -> String {
+    let repo = SCOPE_REPO.lock().unwrap();
+    let mut classes = String::new();
+    for i in 0..scope.len() {
+        let atom = scope.atom_at(i as usize);
+        let atom_s = repo.atom_str(atom);
+        if !classes.is_empty() {
+            classes.push(' ');
+        }
+        if let ClassStyle::SpacedPrefixed { prefix } = class_style {
+            classes.push_str(prefix);
+        }
+        classes.push_str(atom_s);
+    }
+    classes
+}
+
+fn escape_html_into(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+/// Highlights `content` under `syntax`, returning one balanced HTML string per input line: every
+/// `<span>` opened while highlighting a line is closed again before that line's string ends, and
+/// whatever scopes are still active at that boundary (mid block-comment, say) are reopened at the
+/// start of the next line's string. Empty lines come back with no text, only the still-active
+/// scopes' spans opened and closed again (an empty string when no scope is active).
+pub fn highlight_lines(
+    content: &str,
+    syntax: &SyntaxReference,
+    syntax_set: &SyntaxSet,
+    class_style: ClassStyle,
+) -> Vec<String> {
+    let mut parse_state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut open_scopes: Vec<Scope> = Vec::new();
+    let mut lines = Vec::new();
+
+    for raw_line in LinesWithEndings::from(content) {
+        let line = raw_line.trim_end_matches(['\n', '\r']);
+        let ops = parse_state.parse_line(line, syntax_set).unwrap_or_default();
+
+        let mut html = String::new();
+        for scope in &open_scopes {
+            html.push_str("<span class=\"");
+            html.push_str(&scope_to_classes(*scope, class_style));
+            html.push_str("\">");
+        }
+
+        let mut cursor = 0usize;
+        for (offset, op) in ops {
+            if offset > cursor {
+                escape_html_into(&line[cursor..offset], &mut html);
+                cursor = offset;
+            }
+
+            let _ = stack.apply(&op);
+
+            let common = open_scopes
+                .iter()
+                .zip(stack.scopes.iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+            for _ in common..open_scopes.len() {
+                html.push_str("</span>");
+            }
+            for scope in &stack.scopes[common..] {
+                html.push_str("<span class=\"");
+                html.push_str(&scope_to_classes(*scope, class_style));
+                html.push_str("\">");
+            }
+            open_scopes = stack.scopes.clone();
+        }
+        if cursor < line.len() {
+            escape_html_into(&line[cursor..], &mut html);
+        }
+
+        for _ in 0..open_scopes.len() {
+            html.push_str("</span>");
+        }
+
+        lines.push(html);
+    }
+
+    lines
+}
@@ -0,0 +1,104 @@
+//! Markdown rendering for any README or `.md`/`.markdown` blob `render_text_blob` stores rendered
+//! rather than highlighted. The rendered HTML is not embedded in `TreeView` pages. Only compiled
+//! when the `markdown` feature is enabled, so builds that don't want a Markdown parser pulled in
+//! (and the rendering cost that comes with it) can opt out at build time; there is no runtime
+//! switch to turn it off.
+
+use crate::highlight;
+use comrak::adapters::SyntaxHighlighterAdapter;
+use comrak::{markdown_to_html_with_plugins, ComrakOptions, ComrakPlugins};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use syntect::html::ClassStyle;
+use syntect::parsing::SyntaxSet;
+
+/// Filenames (matched case-insensitively) gawsh treats as a tree's README.
+const README_NAMES: &[&str] = &["readme", "readme.md", "readme.markdown"];
+
+/// Extensions (matched case-insensitively) that mark a blob as Markdown prose even when it isn't
+/// named like a README.
+const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown"];
+
+pub fn is_readme(filename: &str) -> bool {
+    README_NAMES.contains(&filename.to_lowercase().as_str())
+}
+
+/// True for any blob `render_text_blob` should run through [`render_markdown`] instead of the
+/// syntect source highlighter: a README (by name, see [`is_readme`]) or a `.md`/`.markdown` file.
+pub fn is_markdown(filename: &str) -> bool {
+    is_readme(filename)
+        || Path::new(filename)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map_or(false, |ext| {
+                MARKDOWN_EXTENSIONS.contains(&ext.to_lowercase().as_str())
+            })
+}
+
+/// Bridges comrak's fenced-code-block rendering to gawsh's existing syntect `ClassStyle`
+/// pipeline, so Markdown code fences get the same `gawsh-`-prefixed classes (and therefore the
+/// same CSS) as every other highlighted blob on the site.
+struct GawshSyntectAdapter<'a> {
+    syntax_set: &'a SyntaxSet,
+    class_style: ClassStyle,
+}
+
+impl SyntaxHighlighterAdapter for GawshSyntectAdapter<'_> {
+    fn write_highlighted(
+        &self,
+        output: &mut dyn Write,
+        lang: Option<&str>,
+        code: &str,
+    ) -> std::io::Result<()> {
+        let syntax = lang
+            .and_then(|lang| self.syntax_set.find_syntax_by_token(lang))
+            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
+
+        let lines = highlight::highlight_lines(code, syntax, &self.syntax_set, self.class_style);
+        write!(output, "{}", lines.join("\n"))
+    }
+
+    fn write_pre_tag(
+        &self,
+        output: &mut dyn Write,
+        _attributes: HashMap<String, String>,
+    ) -> std::io::Result<()> {
+        write!(output, "<pre class=\"gawsh-readme-code\">")
+    }
+
+    fn write_code_tag(
+        &self,
+        output: &mut dyn Write,
+        _attributes: HashMap<String, String>,
+    ) -> std::io::Result<()> {
+        write!(output, "<code>")
+    }
+}
+
+/// Renders a Markdown blob's content to GitHub-flavored-Markdown HTML (tables, strikethrough,
+/// task lists, autolinks), with fenced code blocks highlighted through the same syntect pipeline
+/// as every other file gawsh renders. Raw HTML embedded in the source is stripped rather than
+/// passed through, since this output is stored as-is as a blob's rendering.
+///
+/// Takes the same `syntax_set` `render_text_blob` already has on hand (the one `build_syntax_set`
+/// assembles from syntect's bundled defaults plus `--extra-syntaxes`) rather than building a fresh
+/// default-only one, so code fences get user-supplied syntaxes too and every parallel call doesn't
+/// redo the same `SyntaxSet::load_defaults_newlines()` work.
+pub fn render_markdown(content: &str, syntax_set: &SyntaxSet) -> String {
+    let mut options = ComrakOptions::default();
+    options.extension.table = true;
+    options.extension.strikethrough = true;
+    options.extension.tasklist = true;
+    options.extension.autolink = true;
+    options.render.unsafe_ = false;
+
+    let adapter = GawshSyntectAdapter {
+        syntax_set,
+        class_style: ClassStyle::SpacedPrefixed { prefix: "gawsh-" },
+    };
+    let mut plugins = ComrakPlugins::default();
+    plugins.render.codefence_syntax_highlighter = Some(&adapter);
+
+    markdown_to_html_with_plugins(content, &options, &plugins)
+}
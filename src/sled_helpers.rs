@@ -1,24 +1,285 @@
-use sled::IVec;
+use fst::Streamer;
+use roaring::RoaringBitmap;
+use std::convert::TryInto;
 
+/// One merge operand as stored in the `oids_todo` tree's tagged op-log encoding.
+///
+/// `Add`/`Delete` payloads are a single interned path id; `Reset` carries a whole serialized
+/// `RoaringBitmap` and discards everything that came before it. This mirrors the op-log encoding
+/// used by compaction-style sled merge functions, and is what lets an incremental crawl emit a
+/// targeted `Delete` for a path that vanished between runs instead of forcing a full reindex.
+const OP_ADD: u8 = 0x00;
+const OP_DELETE: u8 = 0x01;
+const OP_RESET: u8 = 0x02;
+
+/// Discriminant byte prefixing every stored filename-set value, distinguishing the two encodings
+/// `concatenate_merge` chooses between: most blobs in a typical repo map to exactly one path, so
+/// storing a single LEB128 varint (no bitmap container/framing overhead) beats a full
+/// `RoaringBitmap` for the common case. A set is only promoted to `ENC_BITMAP` once a second id is
+/// merged in.
+const ENC_SINGLETON: u8 = 0x00;
+const ENC_BITMAP: u8 = 0x01;
+
+/// Decodes a stored filename-set value (in whichever of the two encodings above
+/// `concatenate_merge` chose) into a `RoaringBitmap` for callers that need to enumerate ids.
+pub fn decode_id_set(bytes: &[u8]) -> RoaringBitmap {
+    match bytes.split_first() {
+        Some((&ENC_SINGLETON, mut rest)) => {
+            let mut bitmap = RoaringBitmap::new();
+            if let Ok(id) = leb128::read::unsigned(&mut rest) {
+                bitmap.insert(id as u32);
+            }
+            bitmap
+        }
+        Some((&ENC_BITMAP, rest)) => RoaringBitmap::deserialize_from(rest).unwrap_or_default(),
+        _ => RoaringBitmap::default(),
+    }
+}
+
+/// Picks the smallest valid encoding (see `ENC_SINGLETON`/`ENC_BITMAP` above) for a finished
+/// filename-set value.
+fn encode_id_set(set: &RoaringBitmap) -> Vec<u8> {
+    if set.len() == 1 {
+        let id = set.iter().next().expect("len() == 1, checked above");
+        let mut out = vec![ENC_SINGLETON];
+        leb128::write::unsigned(&mut out, id as u64)
+            .expect("writing a varint into a Vec<u8> cannot fail");
+        out
+    } else {
+        let mut out = Vec::with_capacity(1 + set.serialized_size());
+        out.push(ENC_BITMAP);
+        set.serialize_into(&mut out)
+            .expect("serializing a RoaringBitmap into a Vec<u8> cannot fail");
+        out
+    }
+}
+
+enum Op {
+    Add(u32),
+    Delete(u32),
+    Reset(RoaringBitmap),
+}
+
+/// Parses a (possibly batched) operand stream into its constituent ops. A `Reset` op's payload is
+/// a complete serialized bitmap and, since nothing useful can trail it, consumes the remainder of
+/// the buffer.
+fn parse_ops(bytes: &[u8]) -> Vec<Op> {
+    let mut ops = Vec::new();
+    let mut cursor = bytes;
+
+    while let Some((tag, rest)) = cursor.split_first() {
+        match *tag {
+            OP_ADD | OP_DELETE => {
+                if rest.len() < 4 {
+                    break;
+                }
+                let (id_bytes, rest) = rest.split_at(4);
+                let id = u32::from_be_bytes(id_bytes.try_into().expect("split_at(4) above"));
+                ops.push(if *tag == OP_ADD {
+                    Op::Add(id)
+                } else {
+                    Op::Delete(id)
+                });
+                cursor = rest;
+            }
+            OP_RESET => {
+                ops.push(Op::Reset(
+                    RoaringBitmap::deserialize_from(rest).unwrap_or_default(),
+                ));
+                cursor = &[];
+            }
+            _ => break, // unrecognized tag; refuse to guess payload length for anything after it
+        }
+    }
+
+    ops
+}
+
+/// Tombstone-aware merge operator for the `oids_todo` tree, mapping each OID to the set of
+/// interned path ids it's known under.
+///
+/// `old_value`, if present, is always a fully-compacted id set in one of the `ENC_SINGLETON`/
+/// `ENC_BITMAP` encodings (the return value of a previous call to this function, read back with
+/// `decode_id_set`). `merged_bytes` is the tagged op-log described above. We scan
+/// it for the last `Reset`, which (together with everything after it) replaces `old_value`
+/// entirely as the starting point; any `Add`/`Delete` ops after that point are then folded forward
+/// onto it. If the accumulated set ends up empty, we return `None` so sled drops the key outright
+/// rather than keeping an entry that points at no paths.
 pub fn concatenate_merge(
     _key: &[u8],              // the key being merged
     old_value: Option<&[u8]>, // the previous value, if one existed
     merged_bytes: &[u8],      // the new bytes being merged in
 ) -> Option<Vec<u8>> {
-    // set the new value, return None to delete
-    let mut ret = old_value.map(|ov| ov.to_vec()).unwrap_or_else(Vec::new);
+    let ops = parse_ops(merged_bytes);
+    let last_reset = ops.iter().rposition(|op| matches!(op, Op::Reset(_)));
+
+    let mut acc = match last_reset {
+        Some(idx) => match &ops[idx] {
+            Op::Reset(bitmap) => bitmap.clone(),
+            _ => unreachable!("rposition only matches Op::Reset"),
+        },
+        None => old_value.map(decode_id_set).unwrap_or_default(),
+    };
 
-    for fname in ret.split(|c| c == &0) {
-        if fname == merged_bytes {
-            return Some(ret);
+    let fold_from = last_reset.map_or(0, |idx| idx + 1);
+    for op in &ops[fold_from..] {
+        match op {
+            Op::Add(id) => {
+                acc.insert(*id);
+            }
+            Op::Delete(id) => {
+                acc.remove(*id);
+            }
+            Op::Reset(bitmap) => acc = bitmap.clone(), // not reached: folding starts after the last Reset
         }
     }
 
-    if ret.len() > 0 {
-        ret.push(0);
+    if acc.is_empty() {
+        return None;
+    }
+
+    Some(encode_id_set(&acc))
+}
+
+/// Builds the tagged merge payload that adds a single interned path id.
+pub fn add_op(id: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(5);
+    out.push(OP_ADD);
+    out.extend_from_slice(&id.to_be_bytes());
+    out
+}
+
+/// Builds the tagged merge payload that deletes a single interned path id.
+pub fn delete_op(id: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(5);
+    out.push(OP_DELETE);
+    out.extend_from_slice(&id.to_be_bytes());
+    out
+}
+
+/// Merge operator for the `path_search_index` tree's single well-known key. Both `old_value` and
+/// `merged_bytes` are serialized `fst::Set`s (the latter a whole batch of new paths, see
+/// [`batch_path_fst`]); we deserialize both, union their streams, and write the result back out
+/// through `fst::SetBuilder`, giving an ordered, prefix-searchable structure.
+pub fn fst_union_merge(
+    _key: &[u8],
+    old_value: Option<&[u8]>,
+    merged_bytes: &[u8],
+) -> Option<Vec<u8>> {
+    let old_set = old_value.and_then(|bytes| fst::Set::new(bytes.to_vec()).ok());
+    let new_set = fst::Set::new(merged_bytes.to_vec()).ok()?;
+
+    let mut op_builder = fst::set::OpBuilder::new();
+    if let Some(ref old_set) = old_set {
+        op_builder = op_builder.add(old_set.stream());
+    }
+    op_builder = op_builder.add(new_set.stream());
+
+    let mut out = Vec::new();
+    let mut builder = fst::SetBuilder::new(&mut out).ok()?;
+    builder.extend_stream(op_builder.union()).ok()?;
+    builder.finish().ok()?;
+
+    Some(out)
+}
+
+/// Serializes a whole batch of newly-seen paths into a single `fst::Set`, suitable as one merge
+/// operand for [`fst_union_merge`]. `SetBuilder` requires its inserts in sorted order, so `paths`
+/// is sorted (and deduplicated) in place first.
+///
+/// Building one `fst::Set` per path and unioning it in with a merge call per path costs a full FST
+/// rebuild for every single path gawsh has ever seen, which is quadratic in the number of unique
+/// paths over a whole-repo crawl. Batching every path discovered by a render into one
+/// `SetBuilder` pass, and unioning that in with a single merge call, turns that into one sort plus
+/// one union per render instead.
+pub fn batch_path_fst(paths: &mut Vec<Vec<u8>>) -> Option<Vec<u8>> {
+    if paths.is_empty() {
+        return None;
+    }
+
+    paths.sort_unstable();
+    paths.dedup();
+
+    let mut out = Vec::new();
+    let mut builder = fst::SetBuilder::new(&mut out).ok()?;
+    for path in paths.iter() {
+        builder.insert(path).ok()?;
     }
+    builder.finish().ok()?;
+    Some(out)
+}
 
-    ret.extend_from_slice(merged_bytes);
+/// The commit metadata stored alongside each OID in the `revs` tree, so rendering the commit log
+/// doesn't have to re-open every commit just to sort and label it.
+pub struct RevMetadata {
+    pub commit_time: i64,
+    pub commit_offset_minutes: i32,
+    pub author: String,
+    pub summary: String,
+}
+
+/// Encodes a commit's time (seconds + UTC offset, matching `git2::Time`), author name, and
+/// summary line into the value stored for its OID in the `revs` tree.
+pub fn encode_rev_metadata(
+    commit_time: i64,
+    commit_offset_minutes: i32,
+    author: &str,
+    summary: &str,
+) -> Vec<u8> {
+    let author_bytes = author.as_bytes();
+    let mut out = Vec::with_capacity(12 + author_bytes.len() + summary.len());
+    out.extend_from_slice(&commit_time.to_be_bytes());
+    out.extend_from_slice(&commit_offset_minutes.to_be_bytes());
+    leb128::write::unsigned(&mut out, author_bytes.len() as u64)
+        .expect("writing a varint into a Vec<u8> cannot fail");
+    out.extend_from_slice(author_bytes);
+    out.extend_from_slice(summary.as_bytes());
+    out
+}
+
+/// Inverse of [`encode_rev_metadata`].
+pub fn decode_rev_metadata(bytes: &[u8]) -> Option<RevMetadata> {
+    if bytes.len() < 12 {
+        return None;
+    }
+    let (commit_time, rest) = bytes.split_at(8);
+    let (commit_offset_minutes, mut rest) = rest.split_at(4);
+
+    let commit_time = i64::from_be_bytes(commit_time.try_into().ok()?);
+    let commit_offset_minutes = i32::from_be_bytes(commit_offset_minutes.try_into().ok()?);
+
+    let author_len = leb128::read::unsigned(&mut rest).ok()? as usize;
+    if rest.len() < author_len {
+        return None;
+    }
+    let (author_bytes, summary_bytes) = rest.split_at(author_len);
+
+    Some(RevMetadata {
+        commit_time,
+        commit_offset_minutes,
+        author: String::from_utf8_lossy(author_bytes).into_owned(),
+        summary: String::from_utf8_lossy(summary_bytes).into_owned(),
+    })
+}
+
+/// Discriminant byte prefixing every value stored in the `oids` rendering tree, distinguishing
+/// `render_text_blob`'s two output shapes: a highlighted-source `RenderedObject` table, versus
+/// Markdown prose rendered straight to HTML by `markdown::render_markdown`.
+const REND_SOURCE: u8 = 0x00;
+const REND_MARKDOWN: u8 = 0x01;
+
+/// Tags `html` as highlighted source for storage in the `oids` tree.
+pub fn encode_source_blob(html: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(1 + html.len());
+    out.push(REND_SOURCE);
+    out.extend_from_slice(html.as_bytes());
+    out
+}
 
-    Some(ret)
+/// Tags `html` as rendered Markdown for storage in the `oids` tree.
+pub fn encode_markdown_blob(html: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(1 + html.len());
+    out.push(REND_MARKDOWN);
+    out.extend_from_slice(html.as_bytes());
+    out
 }